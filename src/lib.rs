@@ -0,0 +1,1192 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::process::Command;
+
+#[cfg(test)]
+mod tests;
+
+/// Formats a slice of displayable items as an english list: "a", "a or b",
+/// "a, b, or c", etc. Used to render the set of tokens a parser error
+/// expected.
+pub struct Or<'a, T: 'a>(pub &'a [T]);
+
+impl<'a, T: fmt::Display> fmt::Display for Or<'a, T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.0.len() {
+      0 => Ok(()),
+      1 => write!(f, "{}", self.0[0]),
+      2 => write!(f, "{} or {}", self.0[0], self.0[1]),
+      _ => {
+        for item in &self.0[..self.0.len() - 1] {
+          write!(f, "{}, ", item)?;
+        }
+        write!(f, "or {}", self.0[self.0.len() - 1])
+      }
+    }
+  }
+}
+
+fn is_name_start(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_whitespace(c: char) -> bool {
+  c == ' ' || c == '\t'
+}
+
+/// A lexical token. `prefix` is the text (almost always whitespace) between
+/// the end of the previous token and the start of this one; every token's
+/// `prefix` concatenated with its `lexeme`, in order, reproduces the
+/// original source text exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+  pub index:  usize,
+  pub line:   usize,
+  pub column: usize,
+  pub prefix: &'a str,
+  pub lexeme: &'a str,
+  pub class:  TokenKind<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind<'a> {
+  Line{},
+  Name,
+  Colon,
+  Equals,
+  StringToken{cooked: &'a str},
+  Comment{comment: &'a str},
+  Indent{indentation: &'a str},
+  Dedent,
+  Eol,
+  Eof,
+}
+
+impl<'a> fmt::Display for TokenKind<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      TokenKind::Line{..}        => write!(f, "a line"),
+      TokenKind::Name            => write!(f, "a name"),
+      TokenKind::Colon           => write!(f, "':'"),
+      TokenKind::Equals          => write!(f, "'='"),
+      TokenKind::StringToken{..} => write!(f, "a string"),
+      TokenKind::Comment{..}     => write!(f, "a comment"),
+      TokenKind::Indent{..}      => write!(f, "an indent"),
+      TokenKind::Dedent          => write!(f, "a dedent"),
+      TokenKind::Eol             => write!(f, "the end of a line"),
+      TokenKind::Eof             => write!(f, "the end of the file"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind<'a> {
+  BadName{name: &'a str},
+  CircularDependency{recipe: &'a str, circle: Vec<&'a str>},
+  CircularVariable{variable: &'a str, circle: Vec<&'a str>},
+  DuplicateArgument{recipe: &'a str, argument: &'a str},
+  DuplicateDependency{recipe: &'a str, dependency: &'a str},
+  DuplicateRecipe{recipe: &'a str, first: usize},
+  DuplicateVariable{variable: &'a str},
+  ExtraLeadingWhitespace,
+  InconsistentLeadingWhitespace{expected: &'a str, found: &'a str},
+  MixedLeadingWhitespace{whitespace: &'a str},
+  OuterShebang,
+  UndefinedVariable{variable: String},
+  UnknownDependency{recipe: &'a str, unknown: &'a str},
+  UnknownStartOfToken,
+  UnexpectedToken{expected: Vec<TokenKind<'a>>, found: TokenKind<'a>},
+  UnterminatedString,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error<'a> {
+  pub text:   &'a str,
+  pub index:  usize,
+  pub line:   usize,
+  pub column: usize,
+  pub width:  Option<usize>,
+  pub kind:   ErrorKind<'a>,
+}
+
+impl<'a> fmt::Display for Error<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "error at line {} column {}: ", self.line + 1, self.column + 1)?;
+    match self.kind {
+      ErrorKind::BadName{name} =>
+        write!(f, "'{}' is not a valid name", name),
+      ErrorKind::CircularDependency{recipe, ref circle} =>
+        write!(f, "recipe `{}` has circular dependency `{}`", recipe, circle.join(" -> ")),
+      ErrorKind::CircularVariable{variable, ref circle} =>
+        write!(f, "variable `{}` has circular definition `{}`", variable, circle.join(" -> ")),
+      ErrorKind::DuplicateArgument{recipe, argument} =>
+        write!(f, "recipe `{}` has duplicate argument `{}`", recipe, argument),
+      ErrorKind::DuplicateDependency{recipe, dependency} =>
+        write!(f, "recipe `{}` has duplicate dependency `{}`", recipe, dependency),
+      ErrorKind::DuplicateRecipe{recipe, first} =>
+        write!(f, "recipe `{}` first defined at index {} is redefined", recipe, first),
+      ErrorKind::DuplicateVariable{variable} =>
+        write!(f, "variable `{}` is defined more than once", variable),
+      ErrorKind::ExtraLeadingWhitespace =>
+        write!(f, "recipe line has extra leading whitespace or ends in a backslash with no line to continue"),
+      ErrorKind::InconsistentLeadingWhitespace{expected, found} =>
+        write!(f, "inconsistent leading whitespace: expected {:?} but found {:?}", expected, found),
+      ErrorKind::MixedLeadingWhitespace{whitespace} =>
+        write!(f, "inconsistent leading whitespace: recipe line contains both spaces and tabs: {:?}", whitespace),
+      ErrorKind::OuterShebang =>
+        write!(f, "a justfile may not start with a shebang"),
+      ErrorKind::UndefinedVariable{ref variable} =>
+        write!(f, "variable `{}` is undefined", variable),
+      ErrorKind::UnknownDependency{recipe, unknown} =>
+        write!(f, "recipe `{}` has unknown dependency `{}`", recipe, unknown),
+      ErrorKind::UnknownStartOfToken =>
+        write!(f, "unknown start of token"),
+      ErrorKind::UnexpectedToken{ref expected, ref found} =>
+        write!(f, "expected {} but found {}", Or(expected), found),
+      ErrorKind::UnterminatedString =>
+        write!(f, "unterminated string"),
+    }
+  }
+}
+
+/// The right-hand side of a top-level variable assignment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression<'a> {
+  /// A literal string, e.g. `a = "value"`.
+  Text(&'a str),
+  /// A reference to another variable, e.g. `a = b`.
+  Variable(&'a str),
+}
+
+#[derive(Debug, Clone)]
+pub struct Recipe<'a> {
+  pub name:              &'a str,
+  pub index:             usize,
+  pub line:              usize,
+  pub column:            usize,
+  pub parameters:        Vec<&'a str>,
+  pub dependencies:      Vec<&'a str>,
+  pub dependency_tokens: Vec<Token<'a>>,
+  pub lines:             Vec<String>,
+  pub shebang:           bool,
+}
+
+impl<'a> fmt::Display for Recipe<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.name)?;
+    for parameter in &self.parameters {
+      write!(f, " {}", parameter)?;
+    }
+    write!(f, ":")?;
+    if !self.dependencies.is_empty() {
+      write!(f, " {}", self.dependencies.join(" "))?;
+    }
+    for line in &self.lines {
+      write!(f, "\n    {}", line)?;
+    }
+    Ok(())
+  }
+}
+
+pub struct Justfile<'a> {
+  pub recipes:     BTreeMap<&'a str, Recipe<'a>>,
+  pub assignments: BTreeMap<&'a str, String>,
+}
+
+/// A single recipe invocation parsed from the command line: the recipe's
+/// name together with the positional arguments to bind to its parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation<'a> {
+  pub name:      &'a str,
+  pub arguments: Vec<&'a str>,
+}
+
+/// Options controlling how `Justfile::run` executes recipes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunOptions {
+  /// If true, print each command line and shebang script that would run, in
+  /// dependency order, without spawning any subprocess.
+  pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub enum RunError<'a> {
+  ArityMismatch{recipe: &'a str, expected: usize, found: usize},
+  Code{recipe: &'a str, code: i32},
+  IoError{recipe: &'a str, io_error: std::io::Error},
+  Signal{recipe: &'a str},
+  UnknownRecipes{recipes: Vec<&'a str>},
+}
+
+impl<'a> fmt::Display for RunError<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      RunError::ArityMismatch{recipe, expected, found} =>
+        write!(f, "recipe `{}` got {} argument{} but takes {}", recipe, found, if found == 1 { "" } else { "s" }, expected),
+      RunError::Code{recipe, code} =>
+        write!(f, "recipe `{}` failed with exit code {}", recipe, code),
+      RunError::IoError{recipe, ref io_error} =>
+        write!(f, "recipe `{}` could not be run: {}", recipe, io_error),
+      RunError::Signal{recipe} =>
+        write!(f, "recipe `{}` was terminated by a signal", recipe),
+      RunError::UnknownRecipes{ref recipes} =>
+        write!(f, "unknown recipe{}: {}", if recipes.len() == 1 { "" } else { "s" }, recipes.join(", ")),
+    }
+  }
+}
+
+/// Validates that `name` matches `/[a-z](-?[a-z])*/`, the restricted set of
+/// names `just` allows, and returns the corresponding `BadName` error at
+/// `token`'s position if it does not.
+fn validate_name<'a>(text: &'a str, token: &Token<'a>) -> Result<(), Error<'a>> {
+  let name = token.lexeme;
+  let mut chars = name.chars();
+
+  let lowercase = |c: char| c.is_ascii() && c.is_alphabetic() && c.is_lowercase();
+
+  let valid = match chars.next() {
+    Some(c) if lowercase(c) => {
+      let mut previous_dash = false;
+      let mut ok = true;
+      for c in chars {
+        if c == '-' {
+          if previous_dash {
+            ok = false;
+            break;
+          }
+          previous_dash = true;
+        } else if lowercase(c) {
+          previous_dash = false;
+        } else {
+          ok = false;
+          break;
+        }
+      }
+      ok && !previous_dash
+    },
+    _ => false,
+  };
+
+  if valid {
+    Ok(())
+  } else {
+    Err(Error{
+      text:   text,
+      index:  token.index,
+      line:   token.line,
+      column: token.column,
+      width:  Some(name.len()),
+      kind:   ErrorKind::BadName{name: name},
+    })
+  }
+}
+
+/// Tokenize `text` into a stream of `Token`s, terminated by a single `Eof`
+/// token. Top-level (unindented) lines are split into words; recipe bodies
+/// (lines following a recipe header, indented relative to it) are tokenized
+/// whole, producing one `Line` token per line of the body.
+pub fn tokenize<'a>(text: &'a str) -> Result<Vec<Token<'a>>, Error<'a>> {
+  let mut tokens = vec![];
+  let mut index = 0;
+  let mut line = 0;
+  let mut indentation: Option<&'a str> = None;
+
+  'lines: while index < text.len() {
+    let line_start = index;
+    let rest = &text[index..];
+    let line_end = match rest.find('\n') {
+      Some(offset) => index + offset,
+      None         => text.len(),
+    };
+    let line_text = &text[line_start..line_end];
+    let blank = line_text.chars().all(is_whitespace);
+
+    if blank {
+      if line_end < text.len() {
+        tokens.push(Token{
+          index:  line_end,
+          line:   line,
+          column: line_end - line_start,
+          prefix: line_text,
+          lexeme: "\n",
+          class:  TokenKind::Eol,
+        });
+        index = line_end + 1;
+        line += 1;
+      } else {
+        // trailing whitespace-only text with no newline: leave it for the
+        // final Eof token's prefix
+        break;
+      }
+      continue;
+    }
+
+    if let Some(current_indentation) = indentation {
+      let whitespace_len = line_text.len() - line_text.trim_start_matches(is_whitespace).len();
+      let found = &line_text[..whitespace_len];
+
+      if whitespace_len == 0 {
+        tokens.push(Token{
+          index:  line_start,
+          line:   line,
+          column: 0,
+          prefix: "",
+          lexeme: "",
+          class:  TokenKind::Dedent,
+        });
+        indentation = None;
+        // fall through: re-process this same line at the top level
+      } else if found.starts_with(current_indentation) {
+        let content_start = line_start + current_indentation.len();
+        tokens.push(Token{
+          index:  content_start,
+          line:   line,
+          column: current_indentation.len(),
+          prefix: &text[line_start..content_start],
+          lexeme: &text[content_start..line_end],
+          class:  TokenKind::Line{},
+        });
+        if line_end < text.len() {
+          tokens.push(Token{
+            index:  line_end,
+            line:   line,
+            column: line_end - line_start,
+            prefix: "",
+            lexeme: "\n",
+            class:  TokenKind::Eol,
+          });
+          index = line_end + 1;
+          line += 1;
+        } else {
+          index = line_end;
+        }
+        continue;
+      } else {
+        return Err(Error{
+          text:   text,
+          index:  line_start,
+          line:   line,
+          column: 0,
+          width:  None,
+          kind:   ErrorKind::InconsistentLeadingWhitespace{expected: current_indentation, found: found},
+        });
+      }
+    }
+
+    if indentation.is_none() {
+      let whitespace_len = line_text.len() - line_text.trim_start_matches(is_whitespace).len();
+      if whitespace_len > 0 {
+        let found = &line_text[..whitespace_len];
+        let all_spaces = found.chars().all(|c| c == ' ');
+        let all_tabs   = found.chars().all(|c| c == '\t');
+        if !all_spaces && !all_tabs {
+          return Err(Error{
+            text:   text,
+            index:  line_start,
+            line:   line,
+            column: 0,
+            width:  None,
+            kind:   ErrorKind::MixedLeadingWhitespace{whitespace: found},
+          });
+        }
+
+        tokens.push(Token{
+          index:  line_start,
+          line:   line,
+          column: 0,
+          prefix: "",
+          lexeme: found,
+          class:  TokenKind::Indent{indentation: found},
+        });
+        indentation = Some(found);
+
+        let content_start = line_start + whitespace_len;
+        tokens.push(Token{
+          index:  content_start,
+          line:   line,
+          column: whitespace_len,
+          prefix: "",
+          lexeme: &text[content_start..line_end],
+          class:  TokenKind::Line{},
+        });
+
+        if line_end < text.len() {
+          tokens.push(Token{
+            index:  line_end,
+            line:   line,
+            column: line_end - line_start,
+            prefix: "",
+            lexeme: "\n",
+            class:  TokenKind::Eol,
+          });
+          index = line_end + 1;
+          line += 1;
+        } else {
+          index = line_end;
+        }
+        continue;
+      }
+    }
+
+    // top-level line: tokenize word by word
+    let mut cursor = line_start;
+    loop {
+      let slice = &text[cursor..line_end];
+      let prefix_len = slice.len() - slice.trim_start_matches(is_whitespace).len();
+      let prefix = &slice[..prefix_len];
+      let token_start = cursor + prefix_len;
+      let column = token_start - line_start;
+
+      if token_start == line_end {
+        if line_end < text.len() {
+          tokens.push(Token{
+            index:  line_end,
+            line:   line,
+            column: column,
+            prefix: prefix,
+            lexeme: "\n",
+            class:  TokenKind::Eol,
+          });
+          break;
+        } else {
+          // true end of file, with no trailing newline: leave `prefix` for
+          // the final Eof token rather than emitting a phantom Eol
+          index = cursor;
+          break 'lines;
+        }
+      }
+
+      let c = text[token_start..].chars().next().unwrap();
+
+      if is_name_start(c) {
+        let word = &text[token_start..line_end];
+        let name_len = word.len() - word.trim_start_matches(is_name_start).len();
+        let lexeme = &word[..name_len];
+        tokens.push(Token{
+          index:  token_start,
+          line:   line,
+          column: column,
+          prefix: prefix,
+          lexeme: lexeme,
+          class:  TokenKind::Name,
+        });
+        cursor = token_start + name_len;
+      } else if c == ':' {
+        tokens.push(Token{
+          index:  token_start,
+          line:   line,
+          column: column,
+          prefix: prefix,
+          lexeme: &text[token_start..token_start + 1],
+          class:  TokenKind::Colon,
+        });
+        cursor = token_start + 1;
+      } else if c == '=' {
+        tokens.push(Token{
+          index:  token_start,
+          line:   line,
+          column: column,
+          prefix: prefix,
+          lexeme: &text[token_start..token_start + 1],
+          class:  TokenKind::Equals,
+        });
+        cursor = token_start + 1;
+      } else if c == '"' {
+        let body = &text[token_start + 1..line_end];
+        match body.find('"') {
+          Some(offset) => {
+            let close = token_start + 1 + offset;
+            tokens.push(Token{
+              index:  token_start,
+              line:   line,
+              column: column,
+              prefix: prefix,
+              lexeme: &text[token_start..close + 1],
+              class:  TokenKind::StringToken{cooked: &text[token_start + 1..close]},
+            });
+            cursor = close + 1;
+          },
+          None => {
+            return Err(Error{
+              text:   text,
+              index:  token_start,
+              line:   line,
+              column: column,
+              width:  Some(line_end - token_start),
+              kind:   ErrorKind::UnterminatedString,
+            });
+          },
+        }
+      } else if c == '#' {
+        let comment = &text[token_start..line_end];
+        let body = &comment[1..];
+        if body.starts_with('!') {
+          return Err(Error{
+            text:   text,
+            index:  token_start,
+            line:   line,
+            column: column,
+            width:  None,
+            kind:   ErrorKind::OuterShebang,
+          });
+        }
+        tokens.push(Token{
+          index:  token_start,
+          line:   line,
+          column: column,
+          prefix: prefix,
+          lexeme: comment,
+          class:  TokenKind::Comment{comment: body},
+        });
+        cursor = line_end;
+      } else {
+        return Err(Error{
+          text:   text,
+          index:  token_start,
+          line:   line,
+          column: column,
+          width:  None,
+          kind:   ErrorKind::UnknownStartOfToken,
+        });
+      }
+    }
+
+    if line_end < text.len() {
+      index = line_end + 1;
+      line += 1;
+    } else {
+      index = line_end;
+    }
+  }
+
+  if indentation.is_some() {
+    tokens.push(Token{
+      index:  index,
+      line:   line,
+      column: 0,
+      prefix: "",
+      lexeme: "",
+      class:  TokenKind::Dedent,
+    });
+  }
+
+  tokens.push(Token{
+    index:  index,
+    line:   line,
+    column: index - text[..index].rfind('\n').map(|i| i + 1).unwrap_or(0),
+    prefix: &text[index..],
+    lexeme: "",
+    class:  TokenKind::Eof,
+  });
+
+  Ok(tokens)
+}
+
+struct Parser<'a> {
+  text:   &'a str,
+  tokens: Vec<Token<'a>>,
+  index:  usize,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> &Token<'a> {
+    &self.tokens[self.index]
+  }
+
+  fn advance(&mut self) -> Token<'a> {
+    let token = self.tokens[self.index].clone();
+    if self.index + 1 < self.tokens.len() {
+      self.index += 1;
+    }
+    token
+  }
+
+  fn accept(&mut self, class: &TokenKind<'a>) -> Option<Token<'a>> {
+    if variant_eq(&self.peek().class, class) {
+      Some(self.advance())
+    } else {
+      None
+    }
+  }
+
+  fn expect(&mut self, class: TokenKind<'a>) -> Result<Token<'a>, Error<'a>> {
+    if let Some(token) = self.accept(&class) {
+      Ok(token)
+    } else {
+      self.unexpected_token(vec![class])
+    }
+  }
+
+  fn unexpected_token<T>(&self, expected: Vec<TokenKind<'a>>) -> Result<T, Error<'a>> {
+    let found = self.peek();
+    Err(Error{
+      text:   self.text,
+      index:  found.index,
+      line:   found.line,
+      column: found.column,
+      width:  Some(if found.lexeme.is_empty() { 1 } else { found.lexeme.len() }),
+      kind:   ErrorKind::UnexpectedToken{expected: expected, found: found.class.clone()},
+    })
+  }
+}
+
+fn variant_eq<'a>(a: &TokenKind<'a>, b: &TokenKind<'a>) -> bool {
+  use TokenKind::*;
+  matches!(
+    (a, b),
+    (&Line{..}, &Line{..})               |
+    (&Name, &Name)                       |
+    (&Colon, &Colon)                     |
+    (&Equals, &Equals)                   |
+    (&StringToken{..}, &StringToken{..}) |
+    (&Comment{..}, &Comment{..})         |
+    (&Indent{..}, &Indent{..})           |
+    (&Dedent, &Dedent)                   |
+    (&Eol, &Eol)                         |
+    (&Eof, &Eof)
+  )
+}
+
+/// Parse `text` as a justfile, returning the fully-resolved `Justfile`:
+/// recipes are checked for duplicate/unknown/circular dependencies, recipe
+/// line indentation is checked for consistency, and top-level variable
+/// assignments are evaluated in dependency order.
+pub fn parse<'a>(text: &'a str) -> Result<Justfile<'a>, Error<'a>> {
+  let tokens = tokenize(text)?;
+  let mut parser = Parser{text: text, tokens: tokens, index: 0};
+
+  let mut recipes: BTreeMap<&'a str, Recipe<'a>> = BTreeMap::new();
+  let mut assignments: BTreeMap<&'a str, Expression<'a>> = BTreeMap::new();
+  let mut assignment_tokens: BTreeMap<&'a str, Token<'a>> = BTreeMap::new();
+  let mut reference_tokens: BTreeMap<&'a str, Token<'a>> = BTreeMap::new();
+
+  loop {
+    // skip blank lines and top-level comments
+    loop {
+      if parser.accept(&TokenKind::Eol).is_some() {
+        continue;
+      }
+      if parser.accept(&TokenKind::Comment{comment: ""}).is_some() {
+        continue;
+      }
+      break;
+    }
+
+    if parser.accept(&TokenKind::Eof).is_some() {
+      break;
+    }
+
+    let name_token = parser.expect(TokenKind::Name)?;
+    validate_name(text, &name_token)?;
+
+    if parser.accept(&TokenKind::Equals).is_some() {
+      let (expression, expression_token) = parse_expression(&mut parser)?;
+
+      if assignment_tokens.contains_key(name_token.lexeme) {
+        return Err(Error{
+          text:   text,
+          index:  name_token.index,
+          line:   name_token.line,
+          column: name_token.column,
+          width:  Some(name_token.lexeme.len()),
+          kind:   ErrorKind::DuplicateVariable{variable: name_token.lexeme},
+        });
+      }
+
+      parser.expect(TokenKind::Eol).or_else(|_| parser.expect(TokenKind::Eof))?;
+
+      if let Expression::Variable(_) = expression {
+        reference_tokens.insert(name_token.lexeme, expression_token);
+      }
+      assignments.insert(name_token.lexeme, expression);
+      assignment_tokens.insert(name_token.lexeme, name_token);
+      continue;
+    }
+
+    // recipe definition: name (parameter)* ':' (dependency)* comment? eol body?
+    let mut parameters = vec![];
+    while let Some(parameter) = parser.accept(&TokenKind::Name) {
+      validate_name(text, &parameter)?;
+      if parameters.contains(&parameter.lexeme) {
+        return Err(Error{
+          text:   text,
+          index:  parameter.index,
+          line:   parameter.line,
+          column: parameter.column,
+          width:  Some(parameter.lexeme.len()),
+          kind:   ErrorKind::DuplicateArgument{recipe: name_token.lexeme, argument: parameter.lexeme},
+        });
+      }
+      parameters.push(parameter.lexeme);
+    }
+
+    if parser.accept(&TokenKind::Colon).is_none() {
+      return parser.unexpected_token(vec![TokenKind::Name, TokenKind::Colon]);
+    }
+
+    let mut dependencies = vec![];
+    let mut dependency_tokens = vec![];
+    while let Some(dependency) = parser.accept(&TokenKind::Name) {
+      validate_name(text, &dependency)?;
+      if dependencies.contains(&dependency.lexeme) {
+        return Err(Error{
+          text:   text,
+          index:  dependency.index,
+          line:   dependency.line,
+          column: dependency.column,
+          width:  Some(dependency.lexeme.len()),
+          kind:   ErrorKind::DuplicateDependency{recipe: name_token.lexeme, dependency: dependency.lexeme},
+        });
+      }
+      dependencies.push(dependency.lexeme);
+      dependency_tokens.push(dependency);
+    }
+
+    parser.accept(&TokenKind::Comment{comment: ""});
+
+    if parser.accept(&TokenKind::Eol).is_none() && parser.accept(&TokenKind::Eof).is_none() {
+      return parser.unexpected_token(vec![TokenKind::Name, TokenKind::Eol, TokenKind::Eof]);
+    }
+
+    let mut lines: Vec<String> = vec![];
+    let mut shebang = false;
+    let mut last_line_token: Option<Token<'a>> = None;
+
+    if parser.accept(&TokenKind::Indent{indentation: ""}).is_some() {
+      loop {
+        if parser.accept(&TokenKind::Eol).is_some() {
+          // blank line within the recipe body
+          continue;
+        }
+
+        if let Some(line_token) = parser.accept(&TokenKind::Line{}) {
+          if lines.is_empty() && line_token.lexeme.starts_with("#!") {
+            shebang = true;
+          }
+
+          if shebang {
+            lines.push(line_token.lexeme.to_string());
+          } else {
+            let stripped = line_token.lexeme.trim_start_matches(is_whitespace);
+            let extra = line_token.lexeme.len() - stripped.len();
+
+            // a line is a continuation of the previous one, joined by a
+            // single space, if it is indented beyond the recipe's
+            // established indentation or if the previous line ends in a
+            // backslash; either way there must be a previous line to
+            // continue
+            if extra > 0 || lines.last().map_or(false, |previous| previous.ends_with('\\')) {
+              match lines.pop() {
+                Some(mut previous) => {
+                  if previous.ends_with('\\') {
+                    previous.pop();
+                    while previous.ends_with(is_whitespace) {
+                      previous.pop();
+                    }
+                  }
+                  previous.push(' ');
+                  previous.push_str(stripped);
+                  lines.push(previous);
+                },
+                None => {
+                  return Err(Error{
+                    text:   text,
+                    index:  line_token.index,
+                    line:   line_token.line,
+                    column: line_token.column,
+                    width:  Some(line_token.lexeme.len()),
+                    kind:   ErrorKind::ExtraLeadingWhitespace,
+                  });
+                },
+              }
+            } else {
+              lines.push(line_token.lexeme.to_string());
+            }
+          }
+
+          last_line_token = Some(line_token.clone());
+
+          if !matches!(parser.peek().class, TokenKind::Dedent) {
+            parser.expect(TokenKind::Eol).or_else(|_| parser.expect(TokenKind::Eof))?;
+          }
+        } else {
+          break;
+        }
+      }
+      parser.expect(TokenKind::Dedent)?;
+    }
+
+    if !shebang {
+      if let Some(last) = lines.last() {
+        if last.ends_with('\\') {
+          let token = last_line_token.as_ref().unwrap();
+          return Err(Error{
+            text:   text,
+            index:  token.index,
+            line:   token.line,
+            column: token.column,
+            width:  Some(token.lexeme.len()),
+            kind:   ErrorKind::ExtraLeadingWhitespace,
+          });
+        }
+      }
+    }
+
+    if let Some(first) = recipes.get(name_token.lexeme) {
+      return Err(Error{
+        text:   text,
+        index:  name_token.index,
+        line:   name_token.line,
+        column: name_token.column,
+        width:  Some(name_token.lexeme.len()),
+        kind:   ErrorKind::DuplicateRecipe{recipe: name_token.lexeme, first: first.index},
+      });
+    }
+
+    recipes.insert(name_token.lexeme, Recipe{
+      name:              name_token.lexeme,
+      index:             name_token.index,
+      line:              name_token.line,
+      column:            name_token.column,
+      parameters:        parameters,
+      dependencies:      dependencies,
+      dependency_tokens: dependency_tokens,
+      lines:             lines,
+      shebang:           shebang,
+    });
+  }
+
+  // check that every dependency refers to a known recipe
+  for recipe in recipes.values() {
+    for (dependency, token) in recipe.dependencies.iter().zip(&recipe.dependency_tokens) {
+      if !recipes.contains_key(dependency) {
+        return Err(Error{
+          text:   text,
+          index:  token.index,
+          line:   token.line,
+          column: token.column,
+          width:  Some(dependency.len()),
+          kind:   ErrorKind::UnknownDependency{recipe: recipe.name, unknown: dependency},
+        });
+      }
+    }
+  }
+
+  // check for circular recipe dependencies
+  for recipe in recipes.values() {
+    resolve_recipe_dependencies(text, recipe, &recipes, &mut vec![])?;
+  }
+
+  // evaluate variables in dependency order, detecting undefined variables
+  // and circular variable definitions
+  let mut resolved: BTreeMap<&'a str, String> = BTreeMap::new();
+  let names: Vec<&'a str> = assignments.keys().cloned().collect();
+  for name in names {
+    resolve_variable(text, &assignments, &reference_tokens, name, &mut vec![], &mut resolved)?;
+  }
+
+  // check that every `{{name}}` interpolation used in a recipe body refers
+  // to a defined variable or to one of that recipe's own parameters
+  for recipe in recipes.values() {
+    for line in &recipe.lines {
+      for variable in interpolations(line) {
+        if !recipe.parameters.contains(&variable) && !resolved.contains_key(variable) {
+          return Err(Error{
+            text:   text,
+            index:  recipe.index,
+            line:   recipe.line,
+            column: recipe.column,
+            width:  None,
+            kind:   ErrorKind::UndefinedVariable{variable: variable.to_string()},
+          });
+        }
+      }
+    }
+  }
+
+  Ok(Justfile{recipes: recipes, assignments: resolved})
+}
+
+fn parse_expression<'a>(parser: &mut Parser<'a>) -> Result<(Expression<'a>, Token<'a>), Error<'a>> {
+  if let Some(token) = parser.accept(&TokenKind::StringToken{cooked: ""}) {
+    match token.class {
+      TokenKind::StringToken{cooked} => Ok((Expression::Text(cooked), token)),
+      _ => unreachable!(),
+    }
+  } else if let Some(token) = parser.accept(&TokenKind::Name) {
+    validate_name(parser.text, &token)?;
+    let lexeme = token.lexeme;
+    Ok((Expression::Variable(lexeme), token))
+  } else {
+    parser.unexpected_token(vec![TokenKind::StringToken{cooked: ""}, TokenKind::Name])
+  }
+}
+
+/// Resolves `name` to its final string value, recursively resolving any
+/// variable it references. `stack` holds the chain of variables currently
+/// being resolved, used to detect circular definitions; on a cycle, the
+/// error names the first variable in the cycle and points at that
+/// variable's own reference, the edge that starts the cycle.
+fn resolve_variable<'a>(
+  text:              &'a str,
+  assignments:       &BTreeMap<&'a str, Expression<'a>>,
+  reference_tokens:  &BTreeMap<&'a str, Token<'a>>,
+  name:              &'a str,
+  stack:             &mut Vec<&'a str>,
+  resolved:          &mut BTreeMap<&'a str, String>,
+) -> Result<String, Error<'a>> {
+  if let Some(value) = resolved.get(name) {
+    return Ok(value.clone());
+  }
+
+  stack.push(name);
+
+  let value = match assignments[name].clone() {
+    Expression::Text(value) => value.to_string(),
+    Expression::Variable(variable) => {
+      if let Some(position) = stack.iter().position(|&seen| seen == variable) {
+        let mut circle: Vec<&'a str> = stack[position..].to_vec();
+        circle.push(variable);
+        let token = &reference_tokens[stack[position]];
+        return Err(Error{
+          text:   text,
+          index:  token.index,
+          line:   token.line,
+          column: token.column,
+          width:  Some(token.lexeme.len()),
+          kind:   ErrorKind::CircularVariable{variable: stack[position], circle: circle},
+        });
+      }
+
+      if !assignments.contains_key(variable) {
+        let token = &reference_tokens[name];
+        return Err(Error{
+          text:   text,
+          index:  token.index,
+          line:   token.line,
+          column: token.column,
+          width:  Some(variable.len()),
+          kind:   ErrorKind::UndefinedVariable{variable: variable.to_string()},
+        });
+      }
+      resolve_variable(text, assignments, reference_tokens, variable, stack, resolved)?
+    },
+  };
+
+  stack.pop();
+  resolved.insert(name, value.clone());
+  Ok(value)
+}
+
+/// Walks `recipe`'s dependency graph depth-first, returning a
+/// `CircularDependency` error the moment a dependency already on `stack` is
+/// encountered again. The error blames the recipe that declared the
+/// repeated dependency, and points at that dependency's token.
+fn resolve_recipe_dependencies<'a>(
+  text:    &'a str,
+  recipe:  &Recipe<'a>,
+  recipes: &BTreeMap<&'a str, Recipe<'a>>,
+  stack:   &mut Vec<&'a str>,
+) -> Result<(), Error<'a>> {
+  stack.push(recipe.name);
+
+  for (dependency, token) in recipe.dependencies.iter().zip(&recipe.dependency_tokens) {
+    if let Some(position) = stack.iter().position(|&seen| seen == *dependency) {
+      let mut circle: Vec<&'a str> = stack[position..].to_vec();
+      circle.push(dependency);
+      return Err(Error{
+        text:   text,
+        index:  token.index,
+        line:   token.line,
+        column: token.column,
+        width:  Some(dependency.len()),
+        kind:   ErrorKind::CircularDependency{recipe: recipe.name, circle: circle},
+      });
+    }
+    resolve_recipe_dependencies(text, &recipes[dependency], recipes, stack)?;
+  }
+
+  stack.pop();
+
+  Ok(())
+}
+
+/// Returns the names interpolated via `{{name}}` in `line`, in order of
+/// appearance.
+fn interpolations(line: &str) -> Vec<&str> {
+  let mut names = vec![];
+  let mut rest = line;
+  while let Some(start) = rest.find("{{") {
+    if let Some(end) = rest[start..].find("}}") {
+      let name = rest[start + 2..start + end].trim();
+      if !name.is_empty() {
+        names.push(name);
+      }
+      rest = &rest[start + end + 2..];
+    } else {
+      break;
+    }
+  }
+  names
+}
+
+/// Substitutes every `{{name}}` occurrence in `line` with the value bound to
+/// `name`: a recipe argument in `bindings` if one exists, otherwise the
+/// top-level variable of that name in `assignments`.
+fn substitute(line: &str, assignments: &BTreeMap<&str, String>, bindings: &BTreeMap<&str, &str>) -> String {
+  let mut result = String::new();
+  let mut rest = line;
+  while let Some(start) = rest.find("{{") {
+    result.push_str(&rest[..start]);
+    match rest[start..].find("}}") {
+      Some(end) => {
+        let name = rest[start + 2..start + end].trim();
+        if let Some(value) = bindings.get(name) {
+          result.push_str(value);
+        } else if let Some(value) = assignments.get(name) {
+          result.push_str(value);
+        }
+        rest = &rest[start + end + 2..];
+      },
+      None => {
+        result.push_str(&rest[start..]);
+        rest = "";
+        break;
+      },
+    }
+  }
+  result.push_str(rest);
+  result
+}
+
+impl<'a> Justfile<'a> {
+  /// Run the recipes named in `invocations` (and their dependencies), in
+  /// dependency order, binding each invocation's arguments to its recipe's
+  /// parameters. If `options.dry_run` is set, print the commands and
+  /// shebang scripts that would run to stdout instead of running them.
+  pub fn run(&'a self, invocations: &[Invocation<'a>], options: RunOptions) -> Result<(), RunError<'a>> {
+    self.run_to(&mut io::stdout(), invocations, options)
+  }
+
+  fn run_to<W: Write>(&'a self, out: &mut W, invocations: &[Invocation<'a>], options: RunOptions) -> Result<(), RunError<'a>> {
+    let mut unknown = vec![];
+    for invocation in invocations {
+      if !self.recipes.contains_key(invocation.name) {
+        unknown.push(invocation.name);
+      }
+    }
+
+    if !unknown.is_empty() {
+      return Err(RunError::UnknownRecipes{recipes: unknown});
+    }
+
+    for invocation in invocations {
+      let recipe = &self.recipes[invocation.name];
+      if invocation.arguments.len() != recipe.parameters.len() {
+        return Err(RunError::ArityMismatch{
+          recipe:   recipe.name,
+          expected: recipe.parameters.len(),
+          found:    invocation.arguments.len(),
+        });
+      }
+    }
+
+    let mut ran = vec![];
+    for invocation in invocations {
+      self.run_recipe(out, &self.recipes[invocation.name], &invocation.arguments, options, &mut ran)?;
+    }
+
+    Ok(())
+  }
+
+  /// Runs `recipe`'s dependencies (with no arguments, since dependencies are
+  /// named without a parameter list) and then `recipe` itself, with
+  /// `arguments` bound to its parameters.
+  fn run_recipe<W: Write>(&'a self, out: &mut W, recipe: &'a Recipe<'a>, arguments: &[&'a str], options: RunOptions, ran: &mut Vec<&'a str>) -> Result<(), RunError<'a>> {
+    if ran.contains(&recipe.name) {
+      return Ok(());
+    }
+
+    if arguments.len() != recipe.parameters.len() {
+      return Err(RunError::ArityMismatch{
+        recipe:   recipe.name,
+        expected: recipe.parameters.len(),
+        found:    arguments.len(),
+      });
+    }
+
+    let bindings: BTreeMap<&str, &str> = recipe.parameters.iter().cloned().zip(arguments.iter().cloned()).collect();
+
+    for dependency in &recipe.dependencies {
+      self.run_recipe(out, &self.recipes[dependency], &[], options, ran)?;
+    }
+
+    if recipe.shebang {
+      let mut script = String::new();
+      for line in &recipe.lines {
+        script.push_str(&substitute(line, &self.assignments, &bindings));
+        script.push('\n');
+      }
+
+      if options.dry_run {
+        let _ = write!(out, "{}", script);
+      } else {
+        let path = std::env::temp_dir().join(format!("just-{}-{}", std::process::id(), recipe.name));
+        std::fs::write(&path, script).map_err(|io_error| RunError::IoError{recipe: recipe.name, io_error: io_error})?;
+
+        #[cfg(unix)]
+        {
+          use std::os::unix::fs::PermissionsExt;
+          let mut permissions = std::fs::metadata(&path)
+            .map_err(|io_error| RunError::IoError{recipe: recipe.name, io_error: io_error})?
+            .permissions();
+          permissions.set_mode(0o700);
+          std::fs::set_permissions(&path, permissions)
+            .map_err(|io_error| RunError::IoError{recipe: recipe.name, io_error: io_error})?;
+        }
+
+        let status = Command::new(&path)
+          .status()
+          .map_err(|io_error| RunError::IoError{recipe: recipe.name, io_error: io_error})?;
+
+        let _ = std::fs::remove_file(&path);
+
+        match status.code() {
+          Some(0)    => {},
+          Some(code) => return Err(RunError::Code{recipe: recipe.name, code: code}),
+          None       => return Err(RunError::Signal{recipe: recipe.name}),
+        }
+      }
+    } else {
+      for line in &recipe.lines {
+        let quiet = line.starts_with('@');
+        let command = substitute(if quiet { &line[1..] } else { line }, &self.assignments, &bindings);
+
+        if options.dry_run {
+          let _ = writeln!(out, "{}", command);
+          continue;
+        }
+
+        if !quiet {
+          println!("{}", command);
+        }
+
+        let status = Command::new("sh")
+          .arg("-c")
+          .arg(&command)
+          .status()
+          .map_err(|io_error| RunError::IoError{recipe: recipe.name, io_error: io_error})?;
+
+        match status.code() {
+          Some(0)    => {},
+          Some(code) => return Err(RunError::Code{recipe: recipe.name, code: code}),
+          None       => return Err(RunError::Signal{recipe: recipe.name}),
+        }
+      }
+    }
+
+    ran.push(recipe.name);
+    Ok(())
+  }
+}
@@ -1,9 +1,13 @@
 extern crate tempdir;
 
-use super::{Token, Error, ErrorKind, Justfile};
+use super::{Token, Error, ErrorKind, Justfile, Invocation};
 
 use super::TokenKind::*;
 
+fn invocation<'a>(name: &'a str, arguments: Vec<&'a str>) -> Invocation<'a> {
+  Invocation{name: name, arguments: arguments}
+}
+
 fn tokenize_success(text: &str, expected_summary: &str) {
   let tokens = super::tokenize(text).unwrap();
   let roundtrip = tokens.iter().map(|t| {
@@ -36,6 +40,7 @@ fn token_summary(tokens: &[Token]) -> String {
       super::TokenKind::Name        => "N",
       super::TokenKind::Colon       => ":",
       super::TokenKind::Equals      => "=",
+      super::TokenKind::StringToken{..} => "S",
       super::TokenKind::Comment{..} => "#",
       super::TokenKind::Indent{..}  => ">",
       super::TokenKind::Dedent      => "<",
@@ -98,7 +103,13 @@ bob:
   
   tokenize_success(text, "$N:$>*$*$$*$$*$$<N:$>*$<.");
 
-  tokenize_success("a:=#", "N:=#.")
+  tokenize_success("a:=#", "N:=#.");
+
+  tokenize_success("a = \"b\"", "N=S.");
+
+  // a backslash-continued recipe line is still tokenized as two physical
+  // lines; joining them into one logical line happens during parsing
+  tokenize_success("a:\n echo foo \\\n echo bar\n", "N:$>*$*$<.")
 }
 
 #[test]
@@ -191,15 +202,59 @@ z:
 
 
 #[test]
-fn assignment_unimplemented() {
-  let text = "a = z";
+fn assignment() {
+  parse_summary("a = \"1\"\nb = a\nrecipe:\n  echo {{a}} {{b}}\n", "recipe:\n    echo {{a}} {{b}}\n");
+}
+
+#[test]
+fn undefined_variable() {
+  let text = "a = b";
   parse_error(text, Error {
     text:   text,
-    index:  2,
+    index:  4,
     line:   0,
-    column: 2,
+    column: 4,
     width:  Some(1),
-    kind:   ErrorKind::AssignmentUnimplemented
+    kind:   ErrorKind::UndefinedVariable{variable: "b".to_string()},
+  });
+}
+
+#[test]
+fn undefined_variable_in_recipe() {
+  let text = "a:\n  echo {{x}}\n";
+  parse_error(text, Error {
+    text:   text,
+    index:  0,
+    line:   0,
+    column: 0,
+    width:  None,
+    kind:   ErrorKind::UndefinedVariable{variable: "x".to_string()},
+  });
+}
+
+#[test]
+fn circular_variable() {
+  let text = "a = b\nb = a";
+  parse_error(text, Error {
+    text:   text,
+    index:  4,
+    line:   0,
+    column: 4,
+    width:  Some(1),
+    kind:   ErrorKind::CircularVariable{variable: "a", circle: vec!["a", "b", "a"]},
+  });
+}
+
+#[test]
+fn duplicate_variable() {
+  let text = "a = \"1\"\na = \"2\"";
+  parse_error(text, Error {
+    text:   text,
+    index:  8,
+    line:   1,
+    column: 0,
+    width:  Some(1),
+    kind:   ErrorKind::DuplicateVariable{variable: "a"},
   });
 }
 
@@ -338,7 +393,7 @@ a:
  x
 ";
 
-  match parse_success(text).run(&["a"]).unwrap_err() {
+  match parse_success(text).run(&[invocation("a", vec![])], super::RunOptions::default()).unwrap_err() {
     super::RunError::Code{recipe, code} => {
       assert_eq!(recipe, "a");
       assert_eq!(code, 200);
@@ -365,12 +420,50 @@ c: b
   @mv b c
 ";
   super::std::env::set_current_dir(path).expect("failed to set current directory");
-  parse_success(text).run(&["a", "d"]).unwrap();
+  parse_success(text).run(&[invocation("a", vec![]), invocation("d", vec![])], super::RunOptions::default()).unwrap();
+}
+
+#[test]
+fn dry_run() {
+  let tmp = tempdir::TempDir::new("dry_run").unwrap_or_else(|err| panic!("tmpdir: failed to create temporary directory: {}", err));
+  let path = tmp.path().to_str().unwrap_or_else(|| panic!("tmpdir: path was not valid UTF-8")).to_owned();
+  let text = r"
+b: a
+  @mv a b
+
+a:
+  @touch a
+
+d: c
+  @rm c
+
+c: b
+  @mv b c
+";
+  super::std::env::set_current_dir(path).expect("failed to set current directory");
+  let justfile = parse_success(text);
+  let mut output = Vec::new();
+  justfile.run_to(&mut output, &[invocation("a", vec![]), invocation("d", vec![])], super::RunOptions{dry_run: true}).unwrap();
+  assert_eq!(String::from_utf8(output).unwrap(), "touch a\nmv a b\nmv b c\nrm c\n");
+  assert!(!super::std::path::Path::new("a").exists());
+  assert!(!super::std::path::Path::new("b").exists());
+  assert!(!super::std::path::Path::new("c").exists());
+}
+
+#[test]
+fn variable_substitution() {
+  let tmp = tempdir::TempDir::new("variable_substitution").unwrap_or_else(|err| panic!("tmpdir: failed to create temporary directory: {}", err));
+  let path = tmp.path().to_str().unwrap_or_else(|| panic!("tmpdir: path was not valid UTF-8")).to_owned();
+  let text = "name = \"touched\"\na:\n  @touch {{name}}\n";
+  super::std::env::set_current_dir(path).expect("failed to set current directory");
+  parse_success(text).run(&[invocation("a", vec![])], super::RunOptions::default()).unwrap();
+  assert!(super::std::path::Path::new("touched").exists());
 }
 
 #[test]
 fn unknown_recipes() {
-  match parse_success("a:\nb:\nc:").run(&["a", "x", "y", "z"]).unwrap_err() {
+  let invocations = [invocation("a", vec![]), invocation("x", vec![]), invocation("y", vec![]), invocation("z", vec![])];
+  match parse_success("a:\nb:\nc:").run(&invocations, super::RunOptions::default()).unwrap_err() {
     super::RunError::UnknownRecipes{recipes} => assert_eq!(recipes, &["x", "y", "z"]),
     other @ _ => panic!("expected an unknown recipe error, but got: {}", other),
   }
@@ -378,7 +471,7 @@ fn unknown_recipes() {
 
 #[test]
 fn code_error() {
-  match parse_success("fail:\n @function x { return 100; }; x").run(&["fail"]).unwrap_err() {
+  match parse_success("fail:\n @function x { return 100; }; x").run(&[invocation("fail", vec![])], super::RunOptions::default()).unwrap_err() {
     super::RunError::Code{recipe, code} => {
       assert_eq!(recipe, "fail");
       assert_eq!(code, 100);
@@ -388,19 +481,79 @@ fn code_error() {
 }
 
 #[test]
-fn extra_whitespace() {
-  // we might want to make extra leading whitespace a line continuation in the future,
-  // so make it a error for now
-  let text = "a:\n blah\n  blarg";
+fn argument_substitution() {
+  let tmp = tempdir::TempDir::new("argument_substitution").unwrap_or_else(|err| panic!("tmpdir: failed to create temporary directory: {}", err));
+  let path = tmp.path().to_str().unwrap_or_else(|| panic!("tmpdir: path was not valid UTF-8")).to_owned();
+  let text = "a name:\n  @touch {{name}}\n";
+  super::std::env::set_current_dir(path).expect("failed to set current directory");
+  parse_success(text).run(&[invocation("a", vec!["touched"])], super::RunOptions::default()).unwrap();
+  assert!(super::std::path::Path::new("touched").exists());
+}
+
+#[test]
+fn too_few_arguments() {
+  match parse_success("a b c:").run(&[invocation("a", vec!["1"])], super::RunOptions::default()).unwrap_err() {
+    super::RunError::ArityMismatch{recipe, expected, found} => {
+      assert_eq!(recipe, "a");
+      assert_eq!(expected, 2);
+      assert_eq!(found, 1);
+    },
+    other @ _ => panic!("expected an arity mismatch error, but got: {}", other),
+  }
+}
+
+#[test]
+fn too_many_arguments() {
+  match parse_success("a b:").run(&[invocation("a", vec!["1", "2", "3"])], super::RunOptions::default()).unwrap_err() {
+    super::RunError::ArityMismatch{recipe, expected, found} => {
+      assert_eq!(recipe, "a");
+      assert_eq!(expected, 1);
+      assert_eq!(found, 3);
+    },
+    other @ _ => panic!("expected an arity mismatch error, but got: {}", other),
+  }
+}
+
+#[test]
+fn parameterized_dependency_without_arguments() {
+  match parse_success("b c:\n  @touch c\na: b").run(&[invocation("a", vec![])], super::RunOptions::default()).unwrap_err() {
+    super::RunError::ArityMismatch{recipe, expected, found} => {
+      assert_eq!(recipe, "b");
+      assert_eq!(expected, 1);
+      assert_eq!(found, 0);
+    },
+    other @ _ => panic!("expected an arity mismatch error, but got: {}", other),
+  }
+}
+
+#[test]
+fn line_continuation() {
+  // a line indented beyond the recipe's established indentation is a
+  // continuation of the previous line, joined by a single space
+  parse_summary("a:\n blah\n  blarg", "a:\n    blah blarg\n");
+
+  // a line ending in a backslash is joined with the line that follows it,
+  // regardless of that line's indentation
+  parse_summary("a:\n echo foo \\\n echo bar\n", "a:\n    echo foo echo bar\n");
+}
+
+#[test]
+fn dangling_line_continuation() {
+  // a trailing backslash with no following line to continue onto is
+  // genuinely malformed
+  let text = "a:\n blah \\";
   parse_error(text, Error {
     text:   text,
-    index:  10,
-    line:   2,
+    index:  4,
+    line:   1,
     column: 1,
     width:  Some(6),
     kind:   ErrorKind::ExtraLeadingWhitespace
   });
+}
 
+#[test]
+fn extra_whitespace() {
   // extra leading whitespace is okay in a shebang recipe
   parse_success("a:\n #!\n  print(1)");
 }